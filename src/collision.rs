@@ -12,30 +12,148 @@ use bevy::math::{const_vec3, dquat, mat3, quat, vec3};
 use bevy::utils::HashMap;
 
 use crate::math::{AABB, cwise_max, cwise_min, cwise_mul, Interval};
+use crate::rigidbody::{PhysicsWorld, RigidBody};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum ColliderType {
-    Box{extents: Vec3}
+    Box{extents: Vec3},
+    Sphere{radius: f32},
+    /// An arbitrary convex hull, described in collider-local space: its
+    /// vertices, one outward normal per face, and one direction per unique
+    /// edge (used for the edge-edge cross-product SAT axes).
+    Convex{vertices: Vec<Vec3>, face_normals: Vec<Vec3>, edges: Vec<Vec3>},
 }
 
 const BOX_VERTICES: &'static [Vec3] = &[const_vec3!([-1.0, -1.0, -1.0]), const_vec3!([-1.0, -1.0, 1.0]), const_vec3!([-1.0, 1.0, -1.0]), const_vec3!([-1.0, 1.0, 1.0]),
     const_vec3!([1.0, -1.0, -1.0]), const_vec3!([1.0, -1.0, 1.0]), const_vec3!([1.0, 1.0, -1.0]), const_vec3!([1.0, 1.0, 1.0])];
 
-#[derive(Component, Copy, Clone, Debug)]
+#[derive(Component, Clone, Debug)]
 pub struct Collider {
     ty: ColliderType,
+    friction: f32,
 }
 
 impl Collider {
     pub fn I_b(&self) -> Mat3 {
-        match self.ty {
+        match &self.ty {
             ColliderType::Box{extents: v} => Mat3::from_diagonal(vec3(v.y * v.y + v.z * v.z, v.x * v.x + v.z * v.z, v.x * v.x + v.y * v.y) / 12.0),
-            _ => Mat3::ZERO
+            ColliderType::Sphere{radius} => Mat3::from_diagonal(Vec3::splat(0.4 * radius * radius)),
+            // Approximates the hull as equal point masses at its vertices; a
+            // true volumetric inertia would integrate over the enclosed solid.
+            ColliderType::Convex{vertices, ..} => {
+                let n = vertices.len() as f32;
+                let centroid = vertices.iter().copied().sum::<Vec3>() / n;
+
+                let mut i = Mat3::ZERO;
+                for &v in vertices {
+                    let r = v - centroid;
+                    i += Mat3::from_diagonal(Vec3::splat(Vec3::dot(r, r))) - outer(r);
+                }
+
+                i / n
+            }
         }
     }
 
     pub fn new_box(extents: Vec3) -> Self {
-        Self { ty: ColliderType::Box{extents} }
+        Self { ty: ColliderType::Box{extents}, friction: 0.5 }
+    }
+
+    pub fn new_sphere(radius: f32) -> Self {
+        Self { ty: ColliderType::Sphere{radius}, friction: 0.5 }
+    }
+
+    /// Builds a convex collider from a mesh's vertex buffer by brute-force
+    /// hull construction: every vertex triple whose plane has every other
+    /// vertex on one side contributes a face normal, and every vertex pair
+    /// lying together on two of those faces (a hull edge) whose direction is
+    /// not already covered contributes an edge. Fine for the
+    /// vertex counts typical of a collision mesh; not for dense meshes.
+    pub fn from_mesh_convex_hull(vertices: &[Vec3]) -> Self {
+        let verts = vertices.to_vec();
+        let mut face_normals: Vec<Vec3> = Vec::new();
+        let mut face_offsets: Vec<f32> = Vec::new();
+
+        for i in 0..verts.len() {
+            for j in i + 1..verts.len() {
+                for k in j + 1..verts.len() {
+                    let normal = Vec3::cross(verts[j] - verts[i], verts[k] - verts[i]);
+
+                    if normal.length() < 1e-6 {
+                        continue;
+                    }
+
+                    let normal = normal.normalize();
+                    let d = Vec3::dot(normal, verts[i]);
+
+                    let mut on_positive = false;
+                    let mut on_negative = false;
+
+                    for &v in &verts {
+                        let side = Vec3::dot(normal, v) - d;
+                        if side > 1e-4 { on_positive = true; }
+                        if side < -1e-4 { on_negative = true; }
+                    }
+
+                    if on_positive && on_negative {
+                        continue;
+                    }
+
+                    let outward = if on_positive { -normal } else { normal };
+
+                    if !face_normals.iter().any(|&n| Vec3::dot(n, outward) > 1.0 - 1e-4) {
+                        face_normals.push(outward);
+                        face_offsets.push(Vec3::dot(outward, verts[i]));
+                    }
+                }
+            }
+        }
+
+        let mut edges: Vec<Vec3> = Vec::new();
+
+        for i in 0..verts.len() {
+            for j in i + 1..verts.len() {
+                let delta = verts[j] - verts[i];
+
+                if delta.length() < 1e-6 {
+                    continue;
+                }
+
+                let shared_faces = face_normals.iter().zip(&face_offsets)
+                    .filter(|&(&n, &d)| (Vec3::dot(n, verts[i]) - d).abs() < 1e-4 && (Vec3::dot(n, verts[j]) - d).abs() < 1e-4)
+                    .count();
+
+                if shared_faces < 2 {
+                    continue;
+                }
+
+                let dir = delta.normalize();
+
+                if !edges.iter().any(|&e| Vec3::dot(e, dir).abs() > 1.0 - 1e-4) {
+                    edges.push(dir);
+                }
+            }
+        }
+
+        Self {
+            ty: ColliderType::Convex { vertices: verts, face_normals, edges },
+            friction: 0.5,
+        }
+    }
+
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+
+    /// Radius of a sphere bounding the collider, used to judge how far a body
+    /// can travel in one frame before it risks tunneling through something.
+    pub fn bounding_radius(&self) -> f32 {
+        match &self.ty {
+            ColliderType::Box{extents} => (0.5 * *extents).length(),
+            ColliderType::Sphere{radius} => *radius,
+            ColliderType::Convex{vertices, ..} => vertices.iter().fold(0.0f32, |r, v| r.max(v.length())),
+        }
     }
 
     pub fn with_transform<'c, 'gt>(&'c self, transform: &'gt GlobalTransform) -> TransformedCollider<'c, 'gt> {
@@ -43,6 +161,10 @@ impl Collider {
     }
 }
 
+fn outer(v: Vec3) -> Mat3 {
+    mat3(v * v.x, v * v.y, v * v.z)
+}
+
 
 #[derive(Clone, Copy)]
 pub struct TransformedCollider<'c, 'gt> {
@@ -59,82 +181,636 @@ impl<'c, 'gt> TransformedCollider<'c, 'gt> {
     }
 
     pub fn aabb(&self) -> AABB {
-        match self.collider.ty {
+        match &self.collider.ty {
             ColliderType::Box{extents} => {
-                AABB::fit_vertices(BOX_VERTICES.iter().map(|v| self.transform.mul_vec3(0.5 * cwise_mul(extents, *v))))
+                AABB::fit_vertices(BOX_VERTICES.iter().map(|v| self.transform.mul_vec3(0.5 * cwise_mul(*extents, *v))))
+            }
+            ColliderType::Sphere{radius} => {
+                AABB { min: self.transform.translation - Vec3::splat(*radius), max: self.transform.translation + Vec3::splat(*radius) }
             }
+            ColliderType::Convex{vertices, ..} => {
+                AABB::fit_vertices(vertices.iter().map(|v| self.transform.mul_vec3(*v)))
+            }
+        }
+    }
+
+    /// The resting `aabb()` expanded by this frame's swept motion, so the
+    /// broadphase still pairs up fast-moving bodies that haven't touched yet.
+    pub fn swept_aabb(&self, velocity: Vec3, dt: f32) -> AABB {
+        let aabb = self.aabb();
+        let displacement = velocity * dt;
+
+        AABB {
+            min: cwise_min(aabb.min, aabb.min + displacement),
+            max: cwise_max(aabb.max, aabb.max + displacement),
         }
     }
 
-    pub fn collide(self, other: Self) -> Option<CollisionData> {
-        match (self.collider.ty, other.collider.ty) {
+    /// Narrowphase test. `rel_velocity` is `self`'s velocity relative to `other`'s
+    /// and `dt` the remaining frame time; when the boxes aren't yet touching but
+    /// are closing fast enough to meet within `dt`, a speculative contact with a
+    /// negative `depth` (the separation) is returned instead of `None`, so the
+    /// substepped solver can catch the approach before the bodies tunnel through.
+    pub fn collide(self, other: Self, rel_velocity: Vec3, dt: f32) -> Option<CollisionData> {
+        match (self.collider.ty.clone(), other.collider.ty.clone()) {
+            (ColliderType::Sphere{radius: r_self}, ColliderType::Sphere{radius: r_other}) => {
+                let pa = self.transform.translation;
+                let pb = other.transform.translation;
+
+                let delta = pb - pa;
+                let dist = delta.length();
+
+                if dist < 1e-6 {
+                    return None;
+                }
+
+                let normal = delta / dist;
+                let gap = dist - r_self - r_other;
+                let friction = (self.collider.friction * other.collider.friction).sqrt();
+
+                if gap > 0.0 && Vec3::dot(rel_velocity, normal) * dt < gap {
+                    return None;
+                }
+
+                let point = pa + normal * r_self;
+
+                Some(CollisionData {
+                    normal,
+                    friction,
+                    points: vec![ContactPoint { point, depth: -gap }],
+                })
+            },
+            (ColliderType::Sphere{radius}, ColliderType::Convex{vertices, face_normals, ..}) => {
+                let world_vertices: Vec<Vec3> = vertices.iter().map(|&v| other.transform.mul_vec3(v)).collect();
+                let world_normals: Vec<Vec3> = face_normals.iter().map(|&n| other.transform.rotation * n).collect();
+                let center = self.transform.translation;
+                let closest = closest_point_on_hull(&world_vertices, &world_normals, center);
+
+                let delta = closest - center;
+                let dist = delta.length();
+
+                if dist < 1e-6 {
+                    return None;
+                }
+
+                let normal = delta / dist;
+                let gap = dist - radius;
+                let friction = (self.collider.friction * other.collider.friction).sqrt();
+
+                if gap > 0.0 && Vec3::dot(rel_velocity, normal) * dt < gap {
+                    return None;
+                }
+
+                let point = center + normal * radius;
+
+                Some(CollisionData {
+                    normal,
+                    friction,
+                    points: vec![ContactPoint { point, depth: -gap }],
+                })
+            },
+            (ColliderType::Convex{..}, ColliderType::Sphere{..}) => {
+                other.collide(self, -rel_velocity, dt).map(|data| CollisionData { normal: -data.normal, ..data })
+            },
+            (ColliderType::Convex{vertices: self_local, face_normals: self_local_normals, edges: self_local_edges},
+             ColliderType::Convex{vertices: other_local, face_normals: other_local_normals, edges: other_local_edges}) => {
+                let self_vertices: Vec<Vec3> = self_local.iter().map(|&v| self.transform.mul_vec3(v)).collect();
+                let other_vertices: Vec<Vec3> = other_local.iter().map(|&v| other.transform.mul_vec3(v)).collect();
+
+                let self_world_normals: Vec<Vec3> = self_local_normals.iter().map(|&n| self.transform.rotation * n).collect();
+                let other_world_normals: Vec<Vec3> = other_local_normals.iter().map(|&n| other.transform.rotation * n).collect();
+
+                // Axes 0..self_world_normals.len() are self's face normals, the
+                // next block is other's, and the rest are edge-edge cross
+                // products; an axis_index in the first two blocks means the
+                // manifold can be built by clipping an actual face (see below).
+                let edge_axis_start = self_world_normals.len() + other_world_normals.len();
+
+                let mut axes: Vec<Vec3> = Vec::new();
+
+                axes.extend(self_world_normals.iter().copied());
+                axes.extend(other_world_normals.iter().copied());
+
+                for &e1 in &self_local_edges {
+                    for &e2 in &other_local_edges {
+                        axes.push(Vec3::cross(self.transform.rotation * e1, other.transform.rotation * e2));
+                    }
+                }
+
+                let mut normal = Vec3::ZERO;
+                let mut axis_index = 0;
+                let mut minimal_intersection: Option<Interval> = None;
+
+                let mut best_gap: Option<f32> = None;
+                let mut best_gap_axis = Vec3::ZERO;
+                let mut separated = false;
+
+                for (index, axis) in axes.into_iter().enumerate() {
+                    if axis.length() < 1e-6 {
+                        continue;
+                    }
+
+                    let axis = axis.normalize();
+
+                    let self_interval = Interval::vertex_projection(&self_vertices, axis);
+                    let other_interval = Interval::vertex_projection(&other_vertices, axis);
+
+                    match self_interval.intersection(other_interval) {
+                        Some(intersection) if !separated => {
+                            if minimal_intersection.is_none() || minimal_intersection.unwrap().length() > intersection.length() {
+                                minimal_intersection = Some(intersection);
+                                normal = axis;
+                                axis_index = index;
+                            }
+                        }
+                        Some(_) => {}
+                        None => {
+                            separated = true;
+
+                            if let Some(gap) = self_interval.gap(other_interval) {
+                                // The true separation is the maximum over
+                                // directions of the per-axis gap (the support-
+                                // function argument for distance between convex
+                                // sets), so the axis with the *largest* gap is
+                                // the best estimate of both the real distance
+                                // and the actual separating direction.
+                                if best_gap.is_none() || gap > best_gap.unwrap() {
+                                    best_gap = Some(gap);
+                                    best_gap_axis = axis;
+                                }
+                            }
+                        }
+                    }
+                }
+
+                let friction = (self.collider.friction * other.collider.friction).sqrt();
+
+                if separated {
+                    let gap = best_gap?;
+                    let mut axis_n = best_gap_axis;
+
+                    if Vec3::dot(other.transform.translation - self.transform.translation, axis_n) < 0.0 {
+                        axis_n = -axis_n;
+                    }
+
+                    if Vec3::dot(rel_velocity, axis_n) * dt < gap {
+                        return None;
+                    }
+
+                    let point = approx_contact_point(&self_vertices, &other_vertices, axis_n);
+
+                    return Some(CollisionData {
+                        normal: axis_n,
+                        friction,
+                        points: vec![ContactPoint { point, depth: -gap }],
+                    });
+                }
+
+                let depth = minimal_intersection?.length();
+
+                if Vec3::dot(other.transform.translation - self.transform.translation, normal) < 0.0 {
+                    normal = -normal;
+                }
+
+                // A face axis means one hull is (roughly) resting flat against
+                // the other; clip the incident face into the reference face to
+                // get a stable multi-point manifold, same as the box-box case
+                // below. An edge-edge axis keeps the single deepest-point
+                // estimate (`approx_contact_point`) since a hull's `edges` are
+                // deduplicated directions, not anchored segments, so there's no
+                // cheap way to recover the actual contacting segment pair here.
+                if axis_index < edge_axis_start {
+                    let self_faces = convex_faces(&self_vertices, &self_world_normals);
+                    let other_faces = convex_faces(&other_vertices, &other_world_normals);
+
+                    let reference = self_faces.iter()
+                        .max_by(|a, b| Vec3::dot(a.normal, normal).partial_cmp(&Vec3::dot(b.normal, normal)).unwrap());
+                    let incident = other_faces.iter()
+                        .min_by(|a, b| Vec3::dot(a.normal, normal).partial_cmp(&Vec3::dot(b.normal, normal)).unwrap());
+
+                    if let (Some(reference), Some(incident)) = (reference, incident) {
+                        let points = clip_face_contacts(reference, incident);
+
+                        if !points.is_empty() {
+                            return Some(CollisionData { normal, friction, points });
+                        }
+                    }
+                }
+
+                let point = approx_contact_point(&self_vertices, &other_vertices, normal);
+
+                Some(CollisionData {
+                    normal,
+                    friction,
+                    points: vec![ContactPoint { point, depth }],
+                })
+            },
             (ColliderType::Box{extents: self_extents}, ColliderType::Box{extents: other_extents}) => {
 
                 let self_axes = [self.transform.right(), self.transform.up(), self.transform.forward()];
                 let other_axes = [other.transform.right(), other.transform.up(), other.transform.forward()];
 
+                // 6 face axes plus the 9 cross products of an edge direction from
+                // each box, per the standard OBB separating-axis test.
                 let mut axes = vec!();
 
                 axes.extend(self_axes);
                 axes.extend(other_axes);
 
                 for i in 0..3 {
-                    for j in i+1..3 {
-                        axes.push(Vec3::cross(self_axes[i], self_axes[j]));
+                    for j in 0..3 {
+                        axes.push(Vec3::cross(self_axes[i], other_axes[j]));
                     }
                 }
 
-                let mut self_vertices = [Vec3::ZERO; 6];
-                let mut other_vertices = [Vec3::ZERO; 6];
+                let mut self_vertices = [Vec3::ZERO; 8];
+                let mut other_vertices = [Vec3::ZERO; 8];
 
-                for i in 0..6 {
+                for i in 0..8 {
                     self_vertices[i] = self.transform.mul_vec3(0.5 * cwise_mul(self_extents, BOX_VERTICES[i]));
-                    other_vertices[i] = self.transform.mul_vec3(0.5 * cwise_mul(other_extents, BOX_VERTICES[i]));
+                    other_vertices[i] = other.transform.mul_vec3(0.5 * cwise_mul(other_extents, BOX_VERTICES[i]));
                 }
 
                 let mut normal = Vec3::ZERO;
                 let mut minimal_intersection : Option<Interval> = None;
+                let mut axis_index = 0;
+
+                let mut best_gap: Option<f32> = None;
+                let mut best_gap_axis = Vec3::ZERO;
+                let mut separated = false;
 
-                for axis in axes {
+                for (index, axis) in axes.into_iter().enumerate() {
 
                     if axis.length() < 1e-6 {
                         continue;
                     }
 
+                    let axis = axis.normalize();
+
                     let self_interval = Interval::vertex_projection(&self_vertices[..], axis);
                     let other_interval = Interval::vertex_projection(&other_vertices[..], axis);
 
-                    if let Some(intersection) = self_interval.intersection(other_interval) {
-                        if minimal_intersection.is_none() || minimal_intersection.unwrap().length() > intersection.length() {
-                            minimal_intersection = Some(intersection);
-                            normal = axis;
+                    match self_interval.intersection(other_interval) {
+                        Some(intersection) if !separated => {
+                            if minimal_intersection.is_none() || minimal_intersection.unwrap().length() > intersection.length() {
+                                minimal_intersection = Some(intersection);
+                                normal = axis;
+                                axis_index = index;
+                            }
+                        }
+                        Some(_) => {}
+                        None => {
+                            separated = true;
+
+                            if let Some(gap) = self_interval.gap(other_interval) {
+                                // The true separation is the maximum over
+                                // directions of the per-axis gap (the support-
+                                // function argument for distance between convex
+                                // sets), so the axis with the *largest* gap is
+                                // the best estimate of both the real distance
+                                // and the actual separating direction.
+                                if best_gap.is_none() || gap > best_gap.unwrap() {
+                                    best_gap = Some(gap);
+                                    best_gap_axis = axis;
+                                }
+                            }
                         }
-                    } else {
-                        minimal_intersection = None;
-                        break;
                     }
                 }
 
-                minimal_intersection.map(|i| CollisionData {
-                    normal,
-                    depth: i.length(),
-                })
+                let half_self = 0.5 * self_extents;
+                let half_other = 0.5 * other_extents;
+
+                // Combined per the usual geometric mean of the two surfaces' coefficients.
+                let friction = (self.collider.friction * other.collider.friction).sqrt();
+
+                if separated {
+                    // The boxes don't currently overlap; emit a speculative contact
+                    // only if they're closing fast enough to meet within `dt`.
+                    let gap = best_gap?;
+                    let mut axis_n = best_gap_axis;
+
+                    if Vec3::dot(other.transform.translation - self.transform.translation, axis_n) < 0.0 {
+                        axis_n = -axis_n;
+                    }
+
+                    if Vec3::dot(rel_velocity, axis_n) * dt < gap {
+                        return None;
+                    }
+
+                    let point = approx_contact_point(&self_vertices, &other_vertices, axis_n);
+
+                    return Some(CollisionData {
+                        normal: axis_n,
+                        friction,
+                        points: vec![ContactPoint { point, depth: -gap }],
+                    });
+                }
+
+                let depth = minimal_intersection?.length();
+
+                if Vec3::dot(other.transform.translation - self.transform.translation, normal) < 0.0 {
+                    normal = -normal;
+                }
+
+                // An axis from the cross-product block (index 6..15) means the
+                // minimum-penetration axis came from a pair of edges, not a face.
+                if axis_index >= 6 {
+                    let i = (axis_index - 6) / 3;
+                    let j = (axis_index - 6) % 3;
+
+                    let self_point = edge_anchor(self.transform.translation, self_axes, half_self, i, other.transform.translation);
+                    let other_point = edge_anchor(other.transform.translation, other_axes, half_other, j, self.transform.translation);
+
+                    let (pa, pb) = closest_points_on_lines(self_point, self_axes[i], other_point, other_axes[j]);
+
+                    Some(CollisionData {
+                        normal,
+                        friction,
+                        points: vec![ContactPoint { point: (pa + pb) * 0.5, depth }],
+                    })
+                } else {
+                    let self_faces = box_faces(self.transform.translation, self_axes, half_self);
+                    let other_faces = box_faces(other.transform.translation, other_axes, half_other);
+
+                    let reference = self_faces.iter()
+                        .max_by(|a, b| Vec3::dot(a.normal, normal).partial_cmp(&Vec3::dot(b.normal, normal)).unwrap())
+                        .unwrap();
+                    let incident = other_faces.iter()
+                        .min_by(|a, b| Vec3::dot(a.normal, normal).partial_cmp(&Vec3::dot(b.normal, normal)).unwrap())
+                        .unwrap();
+
+                    let mut points = clip_face_contacts(reference, incident);
+
+                    if points.is_empty() {
+                        points.push(ContactPoint {
+                            point: approx_contact_point(&self_vertices, &other_vertices, normal),
+                            depth,
+                        });
+                    }
+
+                    Some(CollisionData { normal, friction, points })
+                }
+            },
+            (ColliderType::Box{extents}, ColliderType::Sphere{..}) | (ColliderType::Box{extents}, ColliderType::Convex{..}) => {
+                let (vertices, face_normals, edges) = box_convex_parts(extents);
+                let as_convex = Collider { ty: ColliderType::Convex{vertices, face_normals, edges}, friction: self.collider.friction };
+
+                as_convex.with_transform(self.transform).collide(other, rel_velocity, dt)
             },
-            _ => todo!()
+            (ColliderType::Sphere{..}, ColliderType::Box{..}) | (ColliderType::Convex{..}, ColliderType::Box{..}) => {
+                other.collide(self, -rel_velocity, dt).map(|data| CollisionData { normal: -data.normal, ..data })
+            },
+        }
+    }
+}
+
+/// Local-space vertices/face-normals/edge-directions for a box, so a `Box`
+/// collider can be routed through the same narrowphase as `ColliderType::Convex`
+/// when paired with a sphere or hull rather than another box.
+fn box_convex_parts(extents: Vec3) -> (Vec<Vec3>, Vec<Vec3>, Vec<Vec3>) {
+    let vertices = BOX_VERTICES.iter().map(|&v| 0.5 * cwise_mul(extents, v)).collect();
+    let face_normals = vec![Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y, Vec3::Z, -Vec3::Z];
+    let edges = vec![Vec3::X, Vec3::Y, Vec3::Z];
+
+    (vertices, face_normals, edges)
+}
+
+/// Picks the corner of `center`'s box that lies on the edge running along
+/// `axes[edge_axis]`, choosing the other two axes' signs to face `towards`.
+fn edge_anchor(center: Vec3, axes: [Vec3; 3], half_extents: Vec3, edge_axis: usize, towards: Vec3) -> Vec3 {
+    let half = [half_extents.x, half_extents.y, half_extents.z];
+    let mut point = center;
+
+    for k in 0..3 {
+        if k != edge_axis {
+            let sign = Vec3::dot(towards - center, axes[k]).signum();
+            point += axes[k] * half[k] * sign;
+        }
+    }
+
+    point
+}
+
+/// Closest points between the infinite lines through `p1`/`p2` along `d1`/`d2`.
+fn closest_points_on_lines(p1: Vec3, d1: Vec3, p2: Vec3, d2: Vec3) -> (Vec3, Vec3) {
+    let r = p1 - p2;
+    let a = Vec3::dot(d1, d1);
+    let e = Vec3::dot(d2, d2);
+    let f = Vec3::dot(d2, r);
+    let b = Vec3::dot(d1, d2);
+    let c = Vec3::dot(d1, r);
+
+    let denom = a * e - b * b;
+    let s = if denom.abs() > 1e-6 { (b * f - c * e) / denom } else { 0.0 };
+    let t = (b * s + f) / e;
+
+    (p1 + d1 * s, p2 + d2 * t)
+}
+
+/// Single-point estimate (deepest vertex pair along `normal`), used for
+/// speculative contacts, for convex edge-edge contacts, and when face
+/// clipping degenerates to an empty polygon.
+fn approx_contact_point(self_vertices: &[Vec3], other_vertices: &[Vec3], normal: Vec3) -> Vec3 {
+    let self_support = self_vertices.iter().cloned()
+        .fold(self_vertices[0], |acc, v| if Vec3::dot(v, normal) > Vec3::dot(acc, normal) { v } else { acc });
+    let other_support = other_vertices.iter().cloned()
+        .fold(other_vertices[0], |acc, v| if Vec3::dot(v, normal) < Vec3::dot(acc, normal) { v } else { acc });
+
+    (self_support + other_support) * 0.5
+}
+
+/// Nearest hull vertex to `point`, used as a last-resort fallback when a hull
+/// has too few vertices on any face's plane to form a polygon for
+/// `closest_point_on_hull` below.
+fn closest_vertex(vertices: &[Vec3], point: Vec3) -> Vec3 {
+    vertices.iter().cloned()
+        .fold(vertices[0], |acc, v| if (v - point).length() < (acc - point).length() { v } else { acc })
+}
+
+/// Closest point to `point` on the hull's surface, checking every face's
+/// polygon and keeping the nearest candidate; a candidate on a face's
+/// boundary is effectively an edge or vertex closest point too, so this
+/// covers all three cases without treating them separately.
+fn closest_point_on_hull(vertices: &[Vec3], world_face_normals: &[Vec3], point: Vec3) -> Vec3 {
+    let faces = convex_faces(vertices, world_face_normals);
+
+    faces.iter()
+        .map(|f| closest_point_on_polygon(&f.vertices, f.normal, point))
+        .min_by(|a, b| (*a - point).length_squared().partial_cmp(&(*b - point).length_squared()).unwrap())
+        .unwrap_or_else(|| closest_vertex(vertices, point))
+}
+
+#[derive(Clone)]
+struct Face {
+    normal: Vec3,
+    vertices: Vec<Vec3>,
+}
+
+/// Builds each hull face's polygon (vertices wound consistently with the
+/// face normal) by, for every `world_face_normals` entry, taking the hull's
+/// support vertices along that direction and sorting them by angle around
+/// the face's centroid. Degenerate faces (fewer than 3 support vertices,
+/// e.g. from a slightly-off normal) are skipped.
+fn convex_faces(vertices: &[Vec3], world_face_normals: &[Vec3]) -> Vec<Face> {
+    world_face_normals.iter().filter_map(|&normal| {
+        let support = vertices.iter()
+            .fold(f32::NEG_INFINITY, |acc, v| acc.max(Vec3::dot(*v, normal)));
+
+        let mut face_vertices: Vec<Vec3> = vertices.iter().cloned()
+            .filter(|v| (Vec3::dot(*v, normal) - support).abs() < 1e-3)
+            .collect();
+
+        if face_vertices.len() < 3 {
+            return None;
+        }
+
+        let centroid = face_vertices.iter().copied().sum::<Vec3>() / face_vertices.len() as f32;
+        let tangent = Vec3::cross(normal, if normal.x.abs() < 0.9 { Vec3::X } else { Vec3::Y }).normalize();
+        let bitangent = Vec3::cross(normal, tangent);
+
+        face_vertices.sort_by(|&a, &b| {
+            let angle_a = Vec3::dot(a - centroid, bitangent).atan2(Vec3::dot(a - centroid, tangent));
+            let angle_b = Vec3::dot(b - centroid, bitangent).atan2(Vec3::dot(b - centroid, tangent));
+            angle_a.partial_cmp(&angle_b).unwrap()
+        });
+
+        Some(Face { normal, vertices: face_vertices })
+    }).collect()
+}
+
+/// Closest point to `point` on a convex polygon: projects onto the polygon's
+/// plane, then clamps to whichever edge segment the projection falls outside
+/// of (or returns the projection itself if it's already inside).
+fn closest_point_on_polygon(polygon: &[Vec3], normal: Vec3, point: Vec3) -> Vec3 {
+    let proj = point - normal * Vec3::dot(point - polygon[0], normal);
+
+    let n = polygon.len();
+    let face_center: Vec3 = polygon.iter().copied().sum::<Vec3>() / n as f32;
+
+    for i in 0..n {
+        let a = polygon[i];
+        let edge = polygon[(i + 1) % n] - a;
+
+        let mut side_normal = Vec3::cross(normal, edge).normalize();
+        if Vec3::dot(face_center - a, side_normal) > 0.0 {
+            side_normal = -side_normal;
+        }
+
+        if Vec3::dot(proj - a, side_normal) > 0.0 {
+            let t = (Vec3::dot(proj - a, edge) / Vec3::dot(edge, edge)).clamp(0.0, 1.0);
+            return a + edge * t;
+        }
+    }
+
+    proj
+}
+
+fn box_faces(center: Vec3, axes: [Vec3; 3], half_extents: Vec3) -> [Face; 6] {
+    let half = [half_extents.x, half_extents.y, half_extents.z];
+    let mut faces = [(); 6].map(|_| Face { normal: Vec3::ZERO, vertices: Vec::new() });
+
+    for i in 0..3 {
+        let j = (i + 1) % 3;
+        let k = (i + 2) % 3;
+
+        for (s, sign) in [1.0f32, -1.0].into_iter().enumerate() {
+            let normal = axes[i] * sign;
+            let face_center = center + normal * half[i];
+
+            faces[i * 2 + s] = Face {
+                normal,
+                vertices: vec![
+                    face_center + axes[j] * half[j] + axes[k] * half[k],
+                    face_center - axes[j] * half[j] + axes[k] * half[k],
+                    face_center - axes[j] * half[j] - axes[k] * half[k],
+                    face_center + axes[j] * half[j] - axes[k] * half[k],
+                ],
+            };
+        }
+    }
+
+    faces
+}
+
+/// Sutherland–Hodgman clip of `polygon` against the half-space `dot(v - plane_point, plane_normal) <= 0`.
+fn clip_polygon(polygon: &[Vec3], plane_point: Vec3, plane_normal: Vec3) -> Vec<Vec3> {
+    let mut output = Vec::new();
+    let n = polygon.len();
+
+    for i in 0..n {
+        let current = polygon[i];
+        let prev = polygon[(i + n - 1) % n];
+
+        let current_dist = Vec3::dot(current - plane_point, plane_normal);
+        let prev_dist = Vec3::dot(prev - plane_point, plane_normal);
+
+        if current_dist <= 0.0 {
+            if prev_dist > 0.0 {
+                let t = prev_dist / (prev_dist - current_dist);
+                output.push(prev + (current - prev) * t);
+            }
+            output.push(current);
+        } else if prev_dist <= 0.0 {
+            let t = prev_dist / (prev_dist - current_dist);
+            output.push(prev + (current - prev) * t);
+        }
+    }
+
+    output
+}
+
+/// Clips the incident face against the side planes of the reference face
+/// (one per reference edge, whatever its vertex count), keeping the vertices
+/// left below the reference plane as contacts.
+fn clip_face_contacts(reference: &Face, incident: &Face) -> Vec<ContactPoint> {
+    let mut polygon = incident.vertices.clone();
+    let ref_n = reference.vertices.len();
+    let face_center: Vec3 = reference.vertices.iter().sum::<Vec3>() / ref_n as f32;
+
+    for i in 0..ref_n {
+        let edge = reference.vertices[(i + 1) % ref_n] - reference.vertices[i];
+        let mut side_normal = Vec3::cross(reference.normal, edge).normalize();
+
+        if Vec3::dot(face_center - reference.vertices[i], side_normal) > 0.0 {
+            side_normal = -side_normal;
+        }
+
+        polygon = clip_polygon(&polygon, reference.vertices[i], side_normal);
+
+        if polygon.is_empty() {
+            return vec![];
         }
     }
+
+    let ref_point = reference.vertices[0];
+
+    polygon.into_iter()
+        .filter_map(|p| {
+            let depth = -Vec3::dot(p - ref_point, reference.normal);
+            (depth > 0.0).then(|| ContactPoint { point: p, depth })
+        })
+        .collect()
 }
 
 #[derive(Clone, Copy, Debug)]
+pub struct ContactPoint {
+    pub point: Vec3,
+    pub depth: f32,
+}
+
+#[derive(Clone, Debug)]
 pub struct CollisionData {
     pub normal: Vec3,
-    pub depth: f32,
+    pub friction: f32,
+    pub points: Vec<ContactPoint>,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct Collision {
-    entity_a: Entity,
-    entity_b: Entity,
+    pub(crate) entity_a: Entity,
+    pub(crate) entity_b: Entity,
+    pub(crate) data: CollisionData,
 }
 
 #[derive(Debug)]
@@ -146,10 +822,15 @@ impl CollisionStore {
     pub fn add_collision(&mut self, entity_a: Entity, entity_b: Entity, data: CollisionData) {
         self.collisions.push(Collision {
             entity_a,
-            entity_b
+            entity_b,
+            data,
         });
     }
 
+    pub fn iter(&self) -> std::slice::Iter<Collision> {
+        self.collisions.iter()
+    }
+
     pub fn clear(&mut self) {
         self.collisions.clear();
     }
@@ -165,12 +846,19 @@ impl CollisionStore {
     }
 }
 
-type ColliderTriple<'a> = (Entity, &'a Collider, &'a GlobalTransform);
+type ColliderQuery<'a> = (Entity, &'a Collider, &'a GlobalTransform, Option<&'a RigidBody>);
 
-pub fn collide(mut collision_store: ResMut<CollisionStore>, mut query: Query<ColliderTriple>) {
+pub fn collide(mut collision_store: ResMut<CollisionStore>, phys_world: Res<PhysicsWorld>, mut query: Query<ColliderQuery>) {
     collision_store.clear();
 
-    let (aabbs, q) : (Vec<_>, Vec<_>) = query.iter().map(|(e, c, t)| (c.with_transform(t).aabb(), (e, c, t))).unzip();
+    let dt = phys_world.dt;
+
+    let (aabbs, q) : (Vec<_>, Vec<_>) = query.iter()
+        .map(|(e, c, t, rb)| {
+            let velocity = rb.map_or(Vec3::ZERO, |rb| rb.velocity);
+            (c.with_transform(t).swept_aabb(velocity, dt), (e, c, t, velocity))
+        })
+        .unzip();
 
     let n = aabbs.len();
 
@@ -198,12 +886,143 @@ pub fn collide(mut collision_store: ResMut<CollisionStore>, mut query: Query<Col
         .fold(per_axis_collisions[0].clone(), |ref s1,s2| s1 & s2);
 
     for &(i, j) in &collisions {
-        let (e1, c1, t1) = q[i];
-        let (e2, c2, t2) = q[j];
+        let (e1, c1, t1, v1) = q[i];
+        let (e2, c2, t2, v2) = q[j];
 
-        if let Some(data) = c1.with_transform(t1).collide(c2.with_transform(t2)) {
+        if let Some(data) = c1.with_transform(t1).collide(c2.with_transform(t2), v1 - v2, dt) {
             collision_store.add_collision(e1, e2, data);
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sphere_sphere_separated_uses_the_true_separation_as_depth() {
+        let a = Collider::new_sphere(0.5);
+        let b = Collider::new_sphere(0.5);
+
+        let transform_a = GlobalTransform::from_xyz(0.0, 0.0, 0.0);
+        let transform_b = GlobalTransform::from_xyz(3.0, 0.0, 0.0);
+
+        let data = a.with_transform(&transform_a)
+            .collide(b.with_transform(&transform_b), vec3(200.0, 0.0, 0.0), 1.0)
+            .expect("closing fast enough should yield a speculative contact");
+
+        assert!(Vec3::dot(data.normal, Vec3::X) > 0.99, "unexpected normal {:?}", data.normal);
+        assert!((data.points[0].depth + 2.0).abs() < 1e-4, "expected depth ≈ -2, got {}", data.points[0].depth);
+    }
+
+    #[test]
+    fn box_box_separated_picks_the_axis_with_the_largest_gap() {
+        // Box A at the origin, box B offset (3, 5, 0): the X axis gives a gap
+        // of 1 but Y gives 3, which is the better estimate of the true ~3.16
+        // separation and the axis actually being closed along.
+        let a = Collider::new_box(vec3(2.0, 2.0, 2.0));
+        let b = Collider::new_box(vec3(2.0, 2.0, 2.0));
+
+        let transform_a = GlobalTransform::from_xyz(0.0, 0.0, 0.0);
+        let transform_b = GlobalTransform::from_xyz(3.0, 5.0, 0.0);
+
+        let data = a.with_transform(&transform_a)
+            .collide(b.with_transform(&transform_b), vec3(0.0, 300.0, 0.0), 1.0)
+            .expect("closing fast enough along the best-gap axis should yield a speculative contact");
+
+        assert!(Vec3::dot(data.normal, Vec3::Y) > 0.99, "expected the Y axis (gap 3), got normal {:?}", data.normal);
+        assert!((data.points[0].depth + 3.0).abs() < 1e-4, "expected depth ≈ -3, got {}", data.points[0].depth);
+    }
+
+    #[test]
+    fn convex_convex_separated_picks_the_axis_with_the_largest_gap() {
+        let a = Collider::from_mesh_convex_hull(BOX_VERTICES);
+        let b = Collider::from_mesh_convex_hull(BOX_VERTICES);
+
+        let transform_a = GlobalTransform::from_xyz(0.0, 0.0, 0.0);
+        let transform_b = GlobalTransform::from_xyz(3.0, 5.0, 0.0);
+
+        let data = a.with_transform(&transform_a)
+            .collide(b.with_transform(&transform_b), vec3(0.0, 300.0, 0.0), 1.0)
+            .expect("closing fast enough along the best-gap axis should yield a speculative contact");
+
+        assert!(Vec3::dot(data.normal, Vec3::Y) > 0.99, "expected the Y axis (gap 3), got normal {:?}", data.normal);
+        assert!((data.points[0].depth + 3.0).abs() < 1e-4, "expected depth ≈ -3, got {}", data.points[0].depth);
+    }
+
+    #[test]
+    fn box_resting_on_box_produces_a_four_point_manifold() {
+        let a = Collider::new_box(vec3(2.0, 2.0, 2.0));
+        let b = Collider::new_box(vec3(2.0, 2.0, 2.0));
+
+        let transform_a = GlobalTransform::from_xyz(0.0, 0.0, 0.0);
+        let transform_b = GlobalTransform::from_xyz(0.0, 1.9, 0.0);
+
+        let data = a.with_transform(&transform_a)
+            .collide(b.with_transform(&transform_b), Vec3::ZERO, 1.0 / 60.0)
+            .expect("overlapping boxes should collide");
+
+        assert_eq!(data.points.len(), 4);
+        for p in &data.points {
+            assert!((p.depth - 0.1).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn clip_face_contacts_keeps_all_points_of_a_fully_overlapping_incident_face() {
+        let reference = Face {
+            normal: Vec3::Y,
+            vertices: vec![vec3(-1.0, 0.0, -1.0), vec3(1.0, 0.0, -1.0), vec3(1.0, 0.0, 1.0), vec3(-1.0, 0.0, 1.0)],
+        };
+        let incident = Face {
+            normal: -Vec3::Y,
+            vertices: vec![vec3(-0.5, -0.2, -0.5), vec3(0.5, -0.2, -0.5), vec3(0.5, -0.2, 0.5), vec3(-0.5, -0.2, 0.5)],
+        };
+
+        let points = clip_face_contacts(&reference, &incident);
+
+        assert_eq!(points.len(), 4);
+        for p in &points {
+            assert!((p.depth - 0.2).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn clip_face_contacts_is_empty_for_a_disjoint_incident_face() {
+        let reference = Face {
+            normal: Vec3::Y,
+            vertices: vec![vec3(-1.0, 0.0, -1.0), vec3(1.0, 0.0, -1.0), vec3(1.0, 0.0, 1.0), vec3(-1.0, 0.0, 1.0)],
+        };
+        let incident = Face {
+            normal: -Vec3::Y,
+            vertices: vec![vec3(5.0, -0.2, 5.0), vec3(6.0, -0.2, 5.0), vec3(6.0, -0.2, 6.0), vec3(5.0, -0.2, 6.0)],
+        };
+
+        assert!(clip_face_contacts(&reference, &incident).is_empty());
+    }
+
+    #[test]
+    fn from_mesh_convex_hull_of_a_cube_has_six_faces_and_three_edge_directions() {
+        let collider = Collider::from_mesh_convex_hull(BOX_VERTICES);
+
+        match collider.ty {
+            ColliderType::Convex { face_normals, edges, .. } => {
+                assert_eq!(face_normals.len(), 6);
+                assert_eq!(edges.len(), 3);
+            }
+            _ => panic!("expected a Convex collider"),
+        }
+    }
+
+    #[test]
+    fn closest_point_on_polygon_clamps_outside_projection_to_the_nearest_edge() {
+        let square = [vec3(-1.0, 0.0, -1.0), vec3(1.0, 0.0, -1.0), vec3(1.0, 0.0, 1.0), vec3(-1.0, 0.0, 1.0)];
+
+        let inside = closest_point_on_polygon(&square, Vec3::Y, vec3(0.5, 3.0, 0.5));
+        assert!((inside - vec3(0.5, 0.0, 0.5)).length() < 1e-5);
+
+        let outside = closest_point_on_polygon(&square, Vec3::Y, vec3(2.0, 3.0, 0.0));
+        assert!((outside - vec3(1.0, 0.0, 0.0)).length() < 1e-5);
+    }
+}
+
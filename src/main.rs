@@ -3,6 +3,7 @@
 mod rigidbody;
 mod collision;
 mod math;
+mod stabilize;
 mod debug;
 
 use bevy::{
@@ -12,7 +13,7 @@ use bevy::{
 };
 use bevy::math::{dquat, mat3, quat, vec3};
 use crate::collision::{Collider, CollisionStore};
-use crate::rigidbody::{PhysicsWorld, RigidBody};
+use crate::rigidbody::{GravityField, PhysicsWorld, RigidBody};
 
 
 pub fn main() {
@@ -21,8 +22,9 @@ pub fn main() {
         //.add_plugin(FrameTimeDiagnosticsPlugin::default())
         //.add_plugin(LogDiagnosticsPlugin::default())
         .add_startup_system(setup)
+        .add_system(collision::collide.before(rigidbody::update))
+        .add_system(stabilize::stabilize.before(rigidbody::update))
         .add_system(rigidbody::update)
-        .add_system(collision::collide)
         .add_system(debug_collision)
         .run();
 }
@@ -58,7 +60,10 @@ pub fn setup(
         material: material.clone(),
         transform: Transform::from_xyz(-0.7, 0.0, 0.0),
         ..Default::default()
-    }).insert(Collider::new_box(vec3(w, h, d)));
+    }).insert(Collider::new_box(vec3(w, h, d))).insert(RigidBody {
+        is_static: true,
+        ..Default::default()
+    });
 
     commands.spawn_bundle(PbrBundle {
         mesh: mesh.clone(),
@@ -85,7 +90,7 @@ pub fn setup(
     });
 
     commands.insert_resource(PhysicsWorld{
-        g: Vec3::ZERO,
+        gravity: GravityField::Uniform(Vec3::ZERO),
         dt: 0.02,
         .. Default::default()
     });
@@ -83,6 +83,17 @@ impl Interval {
     pub fn max(&self) -> f32 {
         self.max
     }
+
+    /// Distance between two disjoint intervals, or `None` if they overlap.
+    pub fn gap(self, other: Interval) -> Option<f32> {
+        if self.max < other.min {
+            Some(other.min - self.max)
+        } else if other.max < self.min {
+            Some(self.min - other.max)
+        } else {
+            None
+        }
+    }
 }
 
 impl Display for Interval {
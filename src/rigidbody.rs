@@ -3,8 +3,9 @@ use bevy::{
     prelude::*,
     math::{Mat3, Vec3},
 };
-use bevy::math::{dquat, mat3, quat, vec3};
-use crate::collision::Collider;
+use bevy::math::{dquat, mat3, quat, vec3, Quat};
+use bevy::utils::HashMap;
+use crate::collision::{Collider, CollisionData, CollisionStore};
 
 use crate::math;
 use crate::math::qmul;
@@ -12,71 +13,373 @@ use crate::math::qmul;
 #[derive(Component)]
 pub struct RigidBody {
     pub mass: f32,
+    pub is_static: bool,
     pub velocity: Vec3,
     pub angular_velocity: Vec3,
     pub force: Vec3,
     pub torque: Vec3,
+    /// Consecutive frames this body has moved further than its own bounding
+    /// radius, i.e. fast enough to risk tunneling through thin colliders.
+    /// `update` reads the frame's maximum across all bodies and substeps
+    /// further in response, solving the frame's contacts in finer steps until
+    /// no body is moving that fast anymore. Contacts are still detected once
+    /// per frame; no new contacts are found between substeps.
+    pub tunneling: u32,
 }
 
+/// Cap on the extra substeps `update` adds on top of `PhysicsWorld::substeps`:
+/// one per consecutive frame the worst body has been flagged as tunneling.
+const TUNNELING_EXTRA_SUBSTEPS: u32 = 4;
+
 impl RigidBody {
     pub fn add_force(&mut self, offset: Vec3, force: Vec3) {
         self.force += force;
         self.torque += Vec3::cross(offset, force);
     }
+
+    pub fn add_torque(&mut self, torque: Vec3) {
+        self.torque += torque;
+    }
+
+    pub fn inv_mass(&self) -> f32 {
+        if self.is_static { 0.0 } else { 1.0 / self.mass }
+    }
 }
 
 impl Default for RigidBody {
     fn default() -> Self {
         Self {
             mass: 1.0,
+            is_static: false,
             velocity: Vec3::ZERO,
             angular_velocity: Vec3::ZERO,
             force: Vec3::ZERO,
             torque: Vec3::ZERO,
+            tunneling: 0,
         }
     }
 }
 
-#[derive(Default)]
 pub struct PhysicsWorld {
     pub dt: f32,
-    pub g: Vec3,
+    pub gravity: GravityField,
     pub t: f32,
+    pub substeps: usize,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        Self {
+            dt: 0.0,
+            gravity: GravityField::default(),
+            t: 0.0,
+            substeps: 1,
+        }
+    }
 }
 
-pub(crate) fn update(mut phys_world: ResMut<PhysicsWorld>, mut query: Query<(&mut RigidBody, &Collider, &mut GlobalTransform)>) {
-    for (mut rb, coll, mut t) in query.iter_mut() {
-        let a = rb.force / rb.mass + phys_world.g;
+/// Source of gravitational acceleration sampled at each body's position,
+/// rather than a single constant pulling every body the same way.
+#[derive(Clone, Copy, Debug)]
+pub enum GravityField {
+    /// Constant acceleration everywhere, the old `g: Vec3` behavior.
+    Uniform(Vec3),
+    /// Inverse-square pull toward `center`, as for orbiting a planet;
+    /// `dist` is clamped to `min_radius` so bodies near/at the center
+    /// don't get an unbounded acceleration.
+    Point { center: Vec3, mu: f32, min_radius: f32 },
+    /// Constant-magnitude pull toward `center`, for bodies resting on the
+    /// surface of a body rather than orbiting it.
+    Radial { center: Vec3, strength: f32 },
+}
 
-        rb.velocity += a * phys_world.dt;
+impl GravityField {
+    pub fn acceleration(&self, p: Vec3) -> Vec3 {
+        match *self {
+            GravityField::Uniform(g) => g,
+            GravityField::Point { center, mu, min_radius } => {
+                let r = p - center;
+                let dist = r.length().max(min_radius);
+                -mu * r / (dist * dist * dist)
+            }
+            GravityField::Radial { center, strength } => {
+                let r = p - center;
+                let dist = r.length();
 
-        let mut w = rb.angular_velocity;
+                if dist < 1e-6 {
+                    Vec3::ZERO
+                } else {
+                    -strength * r / dist
+                }
+            }
+        }
+    }
+}
 
-        let I_b = coll.I_b();
-        let I_b_inv = I_b.inverse();
+impl Default for GravityField {
+    fn default() -> Self {
+        GravityField::Uniform(Vec3::ZERO)
+    }
+}
 
-        let R = Mat3::from_quat(t.rotation);
-        let I = R * I_b * R.inverse();
-        let I_inv = R * I_b.inverse() * R.inverse();
+/// Per-substep working copy of a body's integrated state. The XPBD loop
+/// predicts `translation`/`rotation` forward, lets the contact solver
+/// correct them in place, then derives velocities from the position delta
+/// against `prev_translation`/`prev_rotation`. `base_translation` stays
+/// fixed at the frame's starting position so contacts (detected once per
+/// frame) can track how much penetration they still have left to resolve.
+struct SubstepBody {
+    translation: Vec3,
+    rotation: Quat,
+    prev_translation: Vec3,
+    prev_rotation: Quat,
+    base_translation: Vec3,
+    velocity: Vec3,
+    angular_velocity: Vec3,
+    inv_mass: f32,
+    i_b_inv: Mat3,
+    /// Force/torque accumulated on the `RigidBody` this frame, applied every
+    /// substep (the substep loop never re-reads the component).
+    force: Vec3,
+    torque: Vec3,
+}
 
+impl SubstepBody {
+    fn world_inv_inertia(&self) -> Mat3 {
+        let r = Mat3::from_quat(self.rotation);
+        r * self.i_b_inv * r.inverse()
+    }
+}
 
-        let wb = R.inverse() * w;
-        let f = Vec3::cross(wb, I_b * wb) * phys_world.dt;
-        let J = I_b + phys_world.dt * (math::skew(wb) * I_b - math::skew(I_b * wb));
-        let dwb = J.inverse() * f;
-        w = R * (wb - dwb);
+pub(crate) fn update(
+    mut phys_world: ResMut<PhysicsWorld>,
+    collision_store: Res<CollisionStore>,
+    mut query: Query<(Entity, &mut RigidBody, &Collider, &mut GlobalTransform)>,
+) {
+    // A body that outran its own bounding radius last frame risks tunneling
+    // through thin colliders; subdivide this frame further until it settles.
+    let max_tunneling = query.iter().map(|(_, rb, ..)| rb.tunneling).max().unwrap_or(0);
+    let substeps = phys_world.substeps + max_tunneling.min(TUNNELING_EXTRA_SUBSTEPS) as usize;
+    let dt_sub = phys_world.dt / substeps as f32;
 
-        // explicit  w -= I * Vec3::cross(w, I * w) * phys_world.dt;
+    let mut index = HashMap::default();
+    let mut bodies: Vec<SubstepBody> = Vec::new();
 
-        w += I_inv * rb.torque * phys_world.dt;
+    for (e, rb, coll, t) in query.iter() {
+        index.insert(e, bodies.len());
+        bodies.push(SubstepBody {
+            translation: t.translation,
+            rotation: t.rotation,
+            prev_translation: t.translation,
+            prev_rotation: t.rotation,
+            base_translation: t.translation,
+            velocity: rb.velocity,
+            angular_velocity: rb.angular_velocity,
+            inv_mass: rb.inv_mass(),
+            i_b_inv: coll.I_b().inverse(),
+            force: rb.force,
+            torque: rb.torque,
+        });
+    }
 
-        rb.angular_velocity = w;
-        let d_rot = qmul(quat(w.x, w.y, w.z, 0.0), t.rotation) * 0.5 * phys_world.dt;
-        t.rotation = (t.rotation + d_rot).normalize();
-        t.translation += rb.velocity * phys_world.dt;
+    let contacts: Vec<(usize, usize, CollisionData)> = collision_store.iter()
+        .filter_map(|c| Some((*index.get(&c.entity_a)?, *index.get(&c.entity_b)?, c.data.clone())))
+        .collect();
+
+    for _ in 0..substeps {
+        for b in bodies.iter_mut() {
+            b.prev_translation = b.translation;
+            b.prev_rotation = b.rotation;
+
+            let accel = phys_world.gravity.acceleration(b.translation) + b.force * b.inv_mass;
+            b.velocity += accel * dt_sub;
+            b.translation += b.velocity * dt_sub;
+
+            b.angular_velocity += b.world_inv_inertia() * b.torque * dt_sub;
+
+            let w = b.angular_velocity;
+            let d_rot = qmul(quat(w.x, w.y, w.z, 0.0), b.rotation) * 0.5 * dt_sub;
+            b.rotation = (b.rotation + d_rot).normalize();
+        }
+
+        for (ia, ib, data) in &contacts {
+            solve_contact(&mut bodies, *ia, *ib, data, dt_sub);
+        }
+
+        for b in bodies.iter_mut() {
+            b.velocity = (b.translation - b.prev_translation) / dt_sub;
+
+            let mut dq = qmul(b.rotation, b.prev_rotation.conjugate());
+            if dq.w < 0.0 {
+                dq = dq * -1.0;
+            }
+            b.angular_velocity = 2.0 * Vec3::new(dq.x, dq.y, dq.z) / dt_sub;
+        }
+    }
+
+    for (e, mut rb, coll, mut t) in query.iter_mut() {
+        let b = &bodies[index[&e]];
+        rb.velocity = b.velocity;
+        rb.angular_velocity = b.angular_velocity;
+        t.translation = b.translation;
+        t.rotation = b.rotation;
+
+        if (b.translation - b.base_translation).length() > coll.bounding_radius() {
+            rb.tunneling += 1;
+        } else {
+            rb.tunneling = 0;
+        }
 
         rb.force = Vec3::ZERO;
         rb.torque = Vec3::ZERO;
     }
+
     phys_world.t += phys_world.dt;
-}
\ No newline at end of file
+}
+
+/// Resolves every point in the contact manifold in sequence, pushing the two
+/// bodies apart along `data.normal` until each point's penetration is zero,
+/// distributing the correction by generalized inverse mass as in Müller et
+/// al.'s XPBD position update, then applying Coulomb friction at that point.
+///
+/// `contact.depth` may be negative (a speculative contact detected ahead of
+/// actual touching); the real depth is tracked below as the bodies move.
+fn solve_contact(bodies: &mut [SubstepBody], ia: usize, ib: usize, data: &CollisionData, dt_sub: f32) {
+    let n = data.normal.normalize();
+
+    for contact in &data.points {
+        solve_contact_point(bodies, ia, ib, n, contact.point, contact.depth, data.friction, dt_sub);
+    }
+}
+
+fn solve_contact_point(bodies: &mut [SubstepBody], ia: usize, ib: usize, n: Vec3, point: Vec3, base_depth: f32, friction: f32, dt_sub: f32) {
+    let (a, b) = if ia < ib {
+        let (left, right) = bodies.split_at_mut(ib);
+        (&mut left[ia], &mut right[0])
+    } else {
+        let (left, right) = bodies.split_at_mut(ia);
+        (&mut right[0], &mut left[ib])
+    };
+
+    let r_a = point - a.translation;
+    let r_b = point - b.translation;
+
+    let i_a_inv = a.world_inv_inertia();
+    let i_b_inv = b.world_inv_inertia();
+
+    let w_a = a.inv_mass + Vec3::dot(Vec3::cross(r_a, n), i_a_inv * Vec3::cross(r_a, n));
+    let w_b = b.inv_mass + Vec3::dot(Vec3::cross(r_b, n), i_b_inv * Vec3::cross(r_b, n));
+
+    let w_sum = w_a + w_b;
+
+    if w_sum <= 0.0 {
+        return;
+    }
+
+    // How much `a` and `b` have approached each other along `n` since the
+    // contact was detected at the start of the frame: `depth` grows by this
+    // (or, for a speculative contact, rises from negative toward positive)
+    // as the bodies move toward each other.
+    let approach = Vec3::dot(n, (a.translation - a.base_translation) - (b.translation - b.base_translation));
+    let depth = base_depth + approach;
+
+    let lambda_n = if depth > 0.0 { depth / w_sum } else { 0.0 };
+
+    if lambda_n > 0.0 {
+        let p = lambda_n * n;
+
+        // `n` points from `a` toward `b`, so pushing them apart means moving
+        // `a` backward along `n` and `b` forward along it.
+        a.translation -= p * a.inv_mass;
+        b.translation += p * b.inv_mass;
+
+        a.rotation = apply_angular_correction(a.rotation, i_a_inv, r_a, -p);
+        b.rotation = apply_angular_correction(b.rotation, i_b_inv, r_b, p);
+    }
+
+    // Undo the tangential slip accrued this substep, capped by the Coulomb
+    // friction cone `friction * lambda_n` around the normal correction.
+    let slip = (a.velocity + Vec3::cross(a.angular_velocity, r_a)) * dt_sub
+        - (b.velocity + Vec3::cross(b.angular_velocity, r_b)) * dt_sub;
+    let tangential_slip = slip - Vec3::dot(slip, n) * n;
+    let slip_len = tangential_slip.length();
+
+    if slip_len > 1e-8 {
+        let t = tangential_slip / slip_len;
+
+        let w_a_t = a.inv_mass + Vec3::dot(Vec3::cross(r_a, t), i_a_inv * Vec3::cross(r_a, t));
+        let w_b_t = b.inv_mass + Vec3::dot(Vec3::cross(r_b, t), i_b_inv * Vec3::cross(r_b, t));
+        let w_sum_t = w_a_t + w_b_t;
+
+        if w_sum_t > 0.0 {
+            let lambda_t = (slip_len / w_sum_t).min(friction * lambda_n);
+            let p_t = -t * lambda_t;
+
+            a.translation += p_t * a.inv_mass;
+            b.translation -= p_t * b.inv_mass;
+
+            a.rotation = apply_angular_correction(a.rotation, i_a_inv, r_a, p_t);
+            b.rotation = apply_angular_correction(b.rotation, i_b_inv, r_b, -p_t);
+        }
+    }
+}
+
+fn apply_angular_correction(rotation: Quat, i_inv: Mat3, r: Vec3, p: Vec3) -> Quat {
+    let dw = i_inv * Vec3::cross(r, p);
+    let dq = qmul(quat(dw.x, dw.y, dw.z, 0.0), rotation) * 0.5;
+    (rotation + dq).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::ContactPoint;
+
+    // `i_b_inv: Mat3::ZERO` isolates the linear (non-rotational) part of the
+    // correction so the expected displacement is exact and easy to check.
+    fn point_mass_body(translation: Vec3) -> SubstepBody {
+        SubstepBody {
+            translation,
+            rotation: Quat::IDENTITY,
+            prev_translation: translation,
+            prev_rotation: Quat::IDENTITY,
+            base_translation: translation,
+            velocity: Vec3::ZERO,
+            angular_velocity: Vec3::ZERO,
+            inv_mass: 1.0,
+            i_b_inv: Mat3::ZERO,
+            force: Vec3::ZERO,
+            torque: Vec3::ZERO,
+        }
+    }
+
+    #[test]
+    fn solve_contact_point_pushes_bodies_apart_not_together() {
+        // Two unit boxes overlapping by 0.1 along X.
+        let mut bodies = vec![point_mass_body(Vec3::ZERO), point_mass_body(Vec3::new(1.9, 0.0, 0.0))];
+
+        solve_contact_point(&mut bodies, 0, 1, Vec3::X, Vec3::new(0.95, 0.0, 0.0), 0.1, 0.0, 1.0 / 60.0);
+
+        let gap = bodies[1].translation.x - bodies[0].translation.x;
+        assert!(gap > 1.9, "bodies should separate toward a 2.0 gap, got {gap}");
+    }
+
+    #[test]
+    fn solve_contact_converges_penetration_toward_zero() {
+        let mut bodies = vec![point_mass_body(Vec3::ZERO), point_mass_body(Vec3::new(1.9, 0.0, 0.0))];
+        let data = CollisionData {
+            normal: Vec3::X,
+            friction: 0.0,
+            points: vec![ContactPoint { point: Vec3::new(0.95, 0.0, 0.0), depth: 0.1 }],
+        };
+
+        for _ in 0..3 {
+            solve_contact(&mut bodies, 0, 1, &data, 1.0 / 60.0);
+        }
+
+        let depth = data.points[0].depth
+            + Vec3::dot(Vec3::X, (bodies[0].translation - bodies[0].base_translation) - (bodies[1].translation - bodies[1].base_translation));
+
+        assert!(depth.abs() < 1e-5, "penetration should converge to zero, not diverge, got {depth}");
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::{math::Vec3, prelude::*};
+
+use crate::rigidbody::{PhysicsWorld, RigidBody};
+
+/// Drop-in PID controller that keeps a body's local up axis aligned with
+/// `target_up` by applying corrective torque, e.g. to self-right a vehicle
+/// body after a tip. Its system must run before `rigidbody::update` so the
+/// torque it accumulates is picked up in the same frame's integration.
+#[derive(Component)]
+pub struct StabilizeController {
+    pub target_up: Vec3,
+    pub kp: f32,
+    pub kd: f32,
+    pub ki: f32,
+    pub integral: Vec3,
+}
+
+impl StabilizeController {
+    pub fn new(target_up: Vec3, kp: f32, kd: f32, ki: f32) -> Self {
+        Self { target_up, kp, kd, ki, integral: Vec3::ZERO }
+    }
+}
+
+/// Clamp on `integral` so a body stuck unable to right itself doesn't wind
+/// up an ever-growing torque contribution.
+const INTEGRAL_CLAMP: f32 = 10.0;
+
+pub fn stabilize(
+    phys_world: Res<PhysicsWorld>,
+    mut query: Query<(&mut StabilizeController, &mut RigidBody, &GlobalTransform)>,
+) {
+    let dt = phys_world.dt;
+
+    for (mut controller, mut rb, transform) in query.iter_mut() {
+        let e = Vec3::cross(transform.up(), controller.target_up);
+
+        controller.integral = (controller.integral + e * dt).clamp_length_max(INTEGRAL_CLAMP);
+
+        let torque = pid_torque(&controller, e, rb.angular_velocity);
+
+        rb.add_torque(torque);
+    }
+}
+
+/// The PID law itself, pulled out of `stabilize` so it's testable without an
+/// ECS world: `e` is the orientation error (`cross(current_up, target_up)`)
+/// and `controller.integral` is assumed already updated for this frame.
+fn pid_torque(controller: &StabilizeController, e: Vec3, angular_velocity: Vec3) -> Vec3 {
+    controller.kp * e - controller.kd * angular_velocity + controller.ki * controller.integral
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proportional_term_pushes_torque_toward_target_up() {
+        let controller = StabilizeController::new(Vec3::Y, 2.0, 0.0, 0.0);
+        let e = Vec3::cross(Vec3::X, controller.target_up);
+
+        let torque = pid_torque(&controller, e, Vec3::ZERO);
+
+        assert_eq!(torque, 2.0 * e);
+    }
+
+    #[test]
+    fn derivative_term_damps_existing_spin() {
+        let controller = StabilizeController::new(Vec3::Y, 0.0, 0.5, 0.0);
+
+        let torque = pid_torque(&controller, Vec3::ZERO, Vec3::new(0.0, 0.0, 4.0));
+
+        assert_eq!(torque, Vec3::new(0.0, 0.0, -2.0));
+    }
+
+    #[test]
+    fn integral_clamp_bounds_the_accumulated_error() {
+        let mut controller = StabilizeController::new(Vec3::Y, 0.0, 0.0, 1.0);
+
+        controller.integral = (controller.integral + Vec3::X * 1000.0 * 1.0 / 60.0).clamp_length_max(INTEGRAL_CLAMP);
+
+        assert!(controller.integral.length() <= INTEGRAL_CLAMP + 1e-5);
+    }
+}